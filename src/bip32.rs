@@ -0,0 +1,310 @@
+//! BIP32-style hierarchical deterministic key derivation, so a wallet can
+//! derive all of its deposit/change addresses from a single seed instead of
+//! managing one secret per address.
+
+use hmac::{Hmac, Mac};
+use sdk::Sha256Hash;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use std::fmt;
+use std::str::FromStr;
+
+type HmacSha512 = Hmac<Sha512>;
+
+const HARDENED_BIT: u32 = 1 << 31;
+
+#[derive(Debug)]
+pub enum Error {
+    Secp256k1(secp256k1::Error),
+    InvalidChildNumberFormat,
+    InvalidDerivationPathFormat,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Secp256k1(err) => write!(f, "secp256k1 error: {err}"),
+            Error::InvalidChildNumberFormat => write!(f, "invalid child number"),
+            Error::InvalidDerivationPathFormat => write!(f, "invalid derivation path"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<secp256k1::Error> for Error {
+    fn from(err: secp256k1::Error) -> Self {
+        Error::Secp256k1(err)
+    }
+}
+
+/// A single step of a derivation path: either a normal child index, or a
+/// hardened one (conventionally written with a trailing `'`) that can only
+/// be derived from the private key, never the public key alone.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChildNumber {
+    Normal { index: u32 },
+    Hardened { index: u32 },
+}
+
+impl ChildNumber {
+    pub fn from_normal_idx(index: u32) -> Result<Self, Error> {
+        if index & HARDENED_BIT == 0 {
+            Ok(ChildNumber::Normal { index })
+        } else {
+            Err(Error::InvalidChildNumberFormat)
+        }
+    }
+
+    pub fn from_hardened_idx(index: u32) -> Result<Self, Error> {
+        if index & HARDENED_BIT == 0 {
+            Ok(ChildNumber::Hardened { index })
+        } else {
+            Err(Error::InvalidChildNumberFormat)
+        }
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        matches!(self, ChildNumber::Hardened { .. })
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            ChildNumber::Normal { index } => index,
+            ChildNumber::Hardened { index } => index | HARDENED_BIT,
+        }
+    }
+}
+
+impl FromStr for ChildNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (index, hardened) = match s.strip_suffix(['\'', 'h']) {
+            Some(index) => (index, true),
+            None => (s, false),
+        };
+        let index: u32 = index.parse().map_err(|_| Error::InvalidChildNumberFormat)?;
+        if hardened {
+            ChildNumber::from_hardened_idx(index)
+        } else {
+            ChildNumber::from_normal_idx(index)
+        }
+    }
+}
+
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildNumber::Normal { index } => write!(f, "{index}"),
+            ChildNumber::Hardened { index } => write!(f, "{index}'"),
+        }
+    }
+}
+
+/// A full path from the master key, e.g. `m/44'/0'/0'/0/0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivationPath(Vec<ChildNumber>);
+
+impl DerivationPath {
+    pub fn children(&self) -> &[ChildNumber] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Error::InvalidDerivationPathFormat);
+        }
+        parts
+            .map(ChildNumber::from_str)
+            .collect::<Result<_, _>>()
+            .map(DerivationPath)
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts a key of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// A 4-byte stand-in for the parent's identifier, used only to label which
+/// key a child was derived from (not consensus-critical).
+fn fingerprint(public_key: &PublicKey) -> [u8; 4] {
+    let hash = public_key.serialize().hash();
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExtendedPrivateKey {
+    pub private_key: SecretKey,
+    pub chain_code: [u8; 32],
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+}
+
+impl ExtendedPrivateKey {
+    pub fn new_master(seed: &[u8]) -> Result<Self, Error> {
+        let digest = hmac_sha512(b"Bitcoin seed", seed);
+        let (private_key, chain_code) = digest.split_at(32);
+        Ok(ExtendedPrivateKey {
+            private_key: SecretKey::from_slice(private_key)?,
+            chain_code: chain_code.try_into().unwrap(),
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+        })
+    }
+
+    pub fn public_key<C: Signing>(&self, secp: &Secp256k1<C>) -> PublicKey {
+        PublicKey::from_secret_key(secp, &self.private_key)
+    }
+
+    pub fn derive_priv<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        path: &DerivationPath,
+    ) -> Result<Self, Error> {
+        path.children()
+            .iter()
+            .try_fold(self.clone(), |key, child_number| {
+                key.ckd_priv(secp, *child_number)
+            })
+    }
+
+    fn ckd_priv<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        child_number: ChildNumber,
+    ) -> Result<Self, Error> {
+        let mut mac =
+            HmacSha512::new_from_slice(&self.chain_code).expect("HMAC accepts a key of any length");
+        match child_number {
+            ChildNumber::Hardened { .. } => {
+                mac.update(&[0u8]);
+                mac.update(&self.private_key.secret_bytes());
+            }
+            ChildNumber::Normal { .. } => {
+                mac.update(&self.public_key(secp).serialize());
+            }
+        }
+        mac.update(&child_number.to_u32().to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let (tweak, chain_code) = digest.split_at(32);
+
+        let tweak = Scalar::from_be_bytes(tweak.try_into().unwrap())?;
+        let private_key = self.private_key.add_tweak(&tweak)?;
+
+        Ok(ExtendedPrivateKey {
+            private_key,
+            chain_code: chain_code.try_into().unwrap(),
+            depth: self.depth + 1,
+            parent_fingerprint: fingerprint(&self.public_key(secp)),
+            child_number: child_number.to_u32(),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct ExtendedPublicKey {
+    pub public_key: PublicKey,
+    pub chain_code: [u8; 32],
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    pub child_number: u32,
+}
+
+impl ExtendedPublicKey {
+    pub fn from_private<C: Signing>(secp: &Secp256k1<C>, private_key: &ExtendedPrivateKey) -> Self {
+        ExtendedPublicKey {
+            public_key: private_key.public_key(secp),
+            chain_code: private_key.chain_code,
+            depth: private_key.depth,
+            parent_fingerprint: private_key.parent_fingerprint,
+            child_number: private_key.child_number,
+        }
+    }
+
+    /// Derives along `path`, which must contain only normal (non-hardened)
+    /// child numbers since hardened children require the private key.
+    pub fn derive_pub<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        path: &DerivationPath,
+    ) -> Result<Self, Error> {
+        path.children()
+            .iter()
+            .try_fold(*self, |key, child_number| key.ckd_pub(secp, *child_number))
+    }
+
+    fn ckd_pub<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        child_number: ChildNumber,
+    ) -> Result<Self, Error> {
+        if child_number.is_hardened() {
+            return Err(Error::InvalidChildNumberFormat);
+        }
+        let mut mac =
+            HmacSha512::new_from_slice(&self.chain_code).expect("HMAC accepts a key of any length");
+        mac.update(&self.public_key.serialize());
+        mac.update(&child_number.to_u32().to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        let (tweak, chain_code) = digest.split_at(32);
+
+        let tweak = Scalar::from_be_bytes(tweak.try_into().unwrap())?;
+        let public_key = self.public_key.add_exp_tweak(secp, &tweak)?;
+
+        Ok(ExtendedPublicKey {
+            public_key,
+            chain_code: chain_code.try_into().unwrap(),
+            depth: self.depth + 1,
+            parent_fingerprint: fingerprint(&self.public_key),
+            child_number: child_number.to_u32(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive_priv_and_derive_pub_agree() {
+        let secp = Secp256k1::new();
+        let master = ExtendedPrivateKey::new_master(b"correct horse battery staple").unwrap();
+        let master_pub = ExtendedPublicKey::from_private(&secp, &master);
+
+        let path: DerivationPath = "m/0/1".parse().unwrap();
+        let child_priv = master.derive_priv(&secp, &path).unwrap();
+        let child_pub = master_pub.derive_pub(&secp, &path).unwrap();
+
+        assert_eq!(child_priv.public_key(&secp), child_pub.public_key);
+
+        // Hardened derivation can't be mirrored from the public key alone,
+        // so `derive_pub` must reject a path containing one.
+        let hardened_path: DerivationPath = "m/44'/0'".parse().unwrap();
+        assert!(master_pub.derive_pub(&secp, &hardened_path).is_err());
+    }
+
+    #[test]
+    fn derivation_path_parses_hardened_and_normal_steps() {
+        let path: DerivationPath = "m/44'/0h/1".parse().unwrap();
+        assert_eq!(
+            path.children().to_vec(),
+            vec![
+                ChildNumber::Hardened { index: 44 },
+                ChildNumber::Hardened { index: 0 },
+                ChildNumber::Normal { index: 1 },
+            ]
+        );
+        assert!("44'/0'".parse::<DerivationPath>().is_err());
+    }
+}
@@ -1,3 +1,5 @@
+mod bip32;
+
 use sdk::{
     Body, Deposit, DepositInput, Header, MainState, RefundInput, Sha256Hash, SideState, Uint256,
     Unlockable, Withdrawal,
@@ -15,25 +17,175 @@ fn main() {
 struct Output {
     amount: u64,
     address: MinimalAddress,
+    /// Relative timelock in block height: this output cannot be spent
+    /// until `current_height - creation_height >= timelock`. `None` means
+    /// spendable as soon as it is connected.
+    timelock: Option<u64>,
 }
 
+/// Unlocks on revealing a preimage that hashes, together with the message
+/// being signed, to the address itself.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-struct MinimalAddress([u8; 32]);
+struct PreimageAddress([u8; 32]);
 
-impl Unlockable for MinimalAddress {
+impl Unlockable for PreimageAddress {
     type Signature = String;
 
-    fn check_signature(&self, signature: &Self::Signature) -> bool {
-        signature.hash() == self.0
+    fn check_signature(&self, message: &Uint256, signature: &Self::Signature) -> bool {
+        (signature.clone(), message.clone()).hash() == self.0
+    }
+}
+
+/// Address backed by real secp256k1 ECDSA signatures, as an alternative to
+/// `PreimageAddress`'s hash-preimage reveal: the address is the hash of a
+/// compressed public key, and a signature only unlocks it by proving
+/// possession of the matching private key over the transaction sighash.
+/// One of the kinds `MinimalAddress` wraps. Keys for addresses like this
+/// are meant to be derived with [`bip32`] rather than generated one at a
+/// time.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Secp256k1Address([u8; 32]);
+
+impl Secp256k1Address {
+    fn from_public_key(public_key: &secp256k1::PublicKey) -> Self {
+        Secp256k1Address(public_key.serialize().hash())
     }
 }
 
-type MinimalSignature = <MinimalAddress as Unlockable>::Signature;
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Secp256k1Signature {
+    public_key: secp256k1::PublicKey,
+    signature: secp256k1::ecdsa::Signature,
+}
+
+impl Unlockable for Secp256k1Address {
+    type Signature = Secp256k1Signature;
+
+    fn check_signature(&self, message: &Uint256, signature: &Self::Signature) -> bool {
+        if Secp256k1Address::from_public_key(&signature.public_key) != *self {
+            return false;
+        }
+        let secp = secp256k1::Secp256k1::verification_only();
+        let message = secp256k1::Message::from_digest(*message.as_ref());
+        secp.verify_ecdsa(&message, &signature.signature, &signature.public_key)
+            .is_ok()
+    }
+}
+
+/// An m-of-n multisig address, for federations and shared-custody deposits:
+/// the address is the hash of the threshold `m` together with the sorted
+/// list of member public keys, and it unlocks on proof of at least `m`
+/// distinct signatures over the sighash, each from a distinct member key.
+/// One of the kinds `MinimalAddress` wraps, alongside `Secp256k1Address`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct MultisigAddress([u8; 32]);
+
+impl MultisigAddress {
+    /// Sorts and dedupes a list of member public keys, so that a key
+    /// listed more than once only ever occupies one signature slot.
+    fn sorted_distinct_keys(
+        mut member_public_keys: Vec<secp256k1::PublicKey>,
+    ) -> Vec<secp256k1::PublicKey> {
+        member_public_keys.sort_by_key(|public_key| public_key.serialize());
+        member_public_keys.dedup();
+        member_public_keys
+    }
+
+    /// Dedupes `member_public_keys` before hashing, so a caller can't
+    /// inflate the number of signature slots a single key occupies by
+    /// listing it more than once: `check_signature` matches signatures
+    /// against member keys one-for-one, and a repeated key would let one
+    /// signer's signature be counted against more than one slot.
+    fn new(threshold: u32, member_public_keys: Vec<secp256k1::PublicKey>) -> Self {
+        let member_public_keys = Self::sorted_distinct_keys(member_public_keys);
+        MultisigAddress((threshold, member_public_keys).hash())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct MultisigSignature {
+    threshold: u32,
+    member_public_keys: Vec<secp256k1::PublicKey>,
+    signatures: Vec<secp256k1::ecdsa::Signature>,
+}
+
+impl Unlockable for MultisigAddress {
+    type Signature = MultisigSignature;
+
+    fn check_signature(&self, message: &Uint256, signature: &Self::Signature) -> bool {
+        if MultisigAddress::new(signature.threshold, signature.member_public_keys.clone()) != *self
+        {
+            return false;
+        }
+
+        let secp = secp256k1::Secp256k1::verification_only();
+        let message = secp256k1::Message::from_digest(*message.as_ref());
+
+        let mut unmatched_keys =
+            MultisigAddress::sorted_distinct_keys(signature.member_public_keys.clone());
+        let mut valid_signatures = 0u32;
+        for sig in &signature.signatures {
+            if let Some(index) = unmatched_keys
+                .iter()
+                .position(|public_key| secp.verify_ecdsa(&message, sig, public_key).is_ok())
+            {
+                unmatched_keys.remove(index);
+                valid_signatures += 1;
+            }
+        }
+        valid_signatures >= signature.threshold
+    }
+}
+
+/// The address type used throughout this chain: `Output` and
+/// `ConditionalOutput` are both locked by one of these, rather than
+/// being generic over which unlocking scheme is in use. Adding a new kind
+/// of address means adding a variant here and to `MinimalSignature`, not
+/// touching any of the types that hold a `MinimalAddress`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum MinimalAddress {
+    Preimage(PreimageAddress),
+    Secp256k1(Secp256k1Address),
+    Multisig(MultisigAddress),
+}
+
+impl Default for MinimalAddress {
+    fn default() -> Self {
+        MinimalAddress::Preimage(PreimageAddress::default())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum MinimalSignature {
+    Preimage(String),
+    Secp256k1(Secp256k1Signature),
+    Multisig(MultisigSignature),
+}
+
+impl Unlockable for MinimalAddress {
+    type Signature = MinimalSignature;
+
+    fn check_signature(&self, message: &Uint256, signature: &Self::Signature) -> bool {
+        match (self, signature) {
+            (MinimalAddress::Preimage(address), MinimalSignature::Preimage(signature)) => {
+                address.check_signature(message, signature)
+            }
+            (MinimalAddress::Secp256k1(address), MinimalSignature::Secp256k1(signature)) => {
+                address.check_signature(message, signature)
+            }
+            (MinimalAddress::Multisig(address), MinimalSignature::Multisig(signature)) => {
+                address.check_signature(message, signature)
+            }
+            _ => false,
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 enum Outpoint {
     Coinbase { block_hash: Uint256, n: usize },
     Regular { txid: Uint256, n: usize },
+    Conditional { txid: Uint256, n: usize },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,18 +199,328 @@ type MinimalRefundInput = RefundInput<MinimalSignature>;
 type MinimalWithdrawal = Withdrawal<MinimalAddress>;
 type MinimalDeposit = Deposit<MinimalAddress>;
 
+/// An oracle that will, at settlement time, publish `attestation`
+/// signatures over each digit of the realized outcome, under keys
+/// announced ahead of time for this contract.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Oracle {
+    announcement_public_key: secp256k1::PublicKey,
+    attestation_public_key: secp256k1::PublicKey,
+}
+
+/// The terms of a DLC-style contract: outcomes are numbers written in base
+/// `base` with exactly `num_digits` digits, which `oracle` will attest to
+/// one digit at a time.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Contract {
+    oracle: Oracle,
+    base: u64,
+    num_digits: u32,
+}
+
+/// A digit prefix of an outcome: every outcome whose leading
+/// `digits.len()` digits equal `digits` falls under this prefix,
+/// regardless of the remaining digits.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct DigitPrefix {
+    digits: Vec<u64>,
+}
+
+fn digits_to_value(digits: &[u64], base: u64) -> u64 {
+    digits.iter().fold(0, |value, &digit| value * base + digit)
+}
+
+/// Decomposes the payout interval `[start, end]` of base-`base`,
+/// `num_digits`-digit outcomes into the minimal set of digit prefixes that
+/// exactly covers it: the longest prefix whose whole subtree of outcomes
+/// lies inside `[start, end]` is emitted directly, and the recursion only
+/// descends into a subtree when it straddles a boundary of the interval.
+/// This yields O(num_digits * base) prefixes rather than one leaf per
+/// outcome.
+fn cover_range(start: u64, end: u64, base: u64, num_digits: u32) -> Vec<DigitPrefix> {
+    fn recurse(
+        prefix: Vec<u64>,
+        base: u64,
+        remaining_digits: u32,
+        start: u64,
+        end: u64,
+        prefixes: &mut Vec<DigitPrefix>,
+    ) {
+        let subtree_size = base.pow(remaining_digits);
+        let subtree_start = digits_to_value(&prefix, base) * subtree_size;
+        let subtree_end = subtree_start + subtree_size - 1;
+
+        if subtree_end < start || subtree_start > end {
+            return;
+        }
+        if subtree_start >= start && subtree_end <= end {
+            prefixes.push(DigitPrefix { digits: prefix });
+            return;
+        }
+        for digit in 0..base {
+            let mut child_prefix = prefix.clone();
+            child_prefix.push(digit);
+            recurse(
+                child_prefix,
+                base,
+                remaining_digits - 1,
+                start,
+                end,
+                prefixes,
+            );
+        }
+    }
+
+    let mut prefixes = Vec::new();
+    recurse(Vec::new(), base, num_digits, start, end, &mut prefixes);
+    prefixes
+}
+
+/// A DLC-style output: payable once `contract`'s oracle attests to an
+/// outcome matching `prefix`, at which point it pays `amount` to
+/// `address`. A contract covering payout interval `[start, end]` is
+/// represented by one `ConditionalOutput` per prefix from `cover_range`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct ConditionalOutput {
+    contract: Contract,
+    prefix: DigitPrefix,
+    amount: u64,
+    address: MinimalAddress,
+}
+
+impl Contract {
+    /// Builds the `ConditionalOutput`s that together pay `amount` to
+    /// `address` if the oracle's attested outcome falls anywhere in
+    /// `[start, end]`, using `cover_range` to decompose the interval into
+    /// its minimal set of digit prefixes.
+    fn conditional_outputs(
+        &self,
+        start: u64,
+        end: u64,
+        amount: u64,
+        address: MinimalAddress,
+    ) -> Vec<ConditionalOutput> {
+        cover_range(start, end, self.base, self.num_digits)
+            .into_iter()
+            .map(|prefix| ConditionalOutput {
+                contract: self.clone(),
+                prefix,
+                amount,
+                address: address.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Spends a `ConditionalOutput` by presenting the oracle's attestation
+/// signatures over the announced outcome digits, plus the owning
+/// address's authorization over the spend.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ConditionalInput {
+    outpoint: Outpoint,
+    attestations: Vec<secp256k1::ecdsa::Signature>,
+    signature: MinimalSignature,
+}
+
+impl ConditionalOutput {
+    /// Verifies that `attestations` are valid signatures by this output's
+    /// oracle over exactly the digits of `prefix`, at their actual
+    /// position in the outcome and under this specific contract. Binding
+    /// to `(contract, position, digit)` rather than the bare digit value
+    /// stops attestations from one contract, or one position, from being
+    /// replayed to settle an outcome the oracle never actually attested
+    /// to.
+    fn attestations_valid(&self, attestations: &[secp256k1::ecdsa::Signature]) -> bool {
+        if attestations.len() != self.prefix.digits.len() {
+            return false;
+        }
+        let secp = secp256k1::Secp256k1::verification_only();
+        self.prefix.digits.iter().enumerate().zip(attestations).all(
+            |((position, digit), attestation)| {
+                let message = (self.contract.clone(), position, *digit).hash();
+                let message = secp256k1::Message::from_digest(*message.as_ref());
+                secp.verify_ecdsa(
+                    &message,
+                    attestation,
+                    &self.contract.oracle.attestation_public_key,
+                )
+                .is_ok()
+            },
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-struct Transaction {
+struct TransactionV0 {
     deposit_inputs: Vec<MinimalDepositInput>,
     refund_inputs: Vec<MinimalRefundInput>,
     inputs: Vec<MinimalInput>,
+    conditional_inputs: Vec<ConditionalInput>,
 
     withdrawals: Vec<MinimalWithdrawal>,
     outputs: Vec<Output>,
+    conditional_outputs: Vec<ConditionalOutput>,
+}
+
+impl TransactionV0 {
+    /// The sighash for the input at `input_index` (counted across
+    /// `deposit_inputs`, then `refund_inputs`, then `inputs`, then
+    /// `conditional_inputs`, in that order). Commits to this transaction's version, every outpoint it
+    /// spends, its full set of outputs and withdrawals, and the index
+    /// itself, so a signature produced for one input cannot be replayed
+    /// against another. Deliberately does not commit to any block-level
+    /// context: the block's digest is a Merkle root over every
+    /// transaction's hash, which is itself a function of each input's
+    /// signature — committing to it here would make the sighash a signer
+    /// needs depend on signatures that don't exist yet.
+    fn sighash(&self, input_index: usize) -> Uint256 {
+        let deposit_outpoints: Vec<_> = self
+            .deposit_inputs
+            .iter()
+            .map(|input| input.outpoint.clone())
+            .collect();
+        let refund_outpoints: Vec<_> = self
+            .refund_inputs
+            .iter()
+            .map(|input| input.outpoint.clone())
+            .collect();
+        let input_outpoints: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| input.outpoint.clone())
+            .collect();
+        let conditional_outpoints: Vec<_> = self
+            .conditional_inputs
+            .iter()
+            .map(|input| input.outpoint.clone())
+            .collect();
+
+        (
+            0u32,
+            deposit_outpoints,
+            refund_outpoints,
+            input_outpoints,
+            conditional_outpoints,
+            self.outputs.clone(),
+            self.withdrawals.clone(),
+            self.conditional_outputs.clone(),
+            input_index,
+        )
+            .hash()
+    }
+}
+
+/// A transaction of a version this node doesn't have rules for yet. Kept
+/// as raw bytes (rather than failing to deserialize the block at all) so
+/// an older node can still store and relay blocks produced by newer nodes;
+/// `MinimalState::validate_block` only accepts one once the matching
+/// version has been activated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ReservedTransaction {
+    version: u32,
+    data: Vec<u8>,
+}
+
+/// `Transaction` is versioned so consensus rules can evolve (e.g. a future
+/// version adding a field layout of its own) without breaking
+/// deserialization of blocks built under an older version.
+#[derive(Debug, Serialize, Deserialize)]
+enum Transaction {
+    V0(TransactionV0),
+    Reserved(ReservedTransaction),
+}
+
+impl Transaction {
+    fn version(&self) -> u32 {
+        match self {
+            Transaction::V0(_) => 0,
+            Transaction::Reserved(reserved) => reserved.version,
+        }
+    }
+
+    fn deposit_inputs(&self) -> &[MinimalDepositInput] {
+        match self {
+            Transaction::V0(tx) => &tx.deposit_inputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn refund_inputs(&self) -> &[MinimalRefundInput] {
+        match self {
+            Transaction::V0(tx) => &tx.refund_inputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn inputs(&self) -> &[MinimalInput] {
+        match self {
+            Transaction::V0(tx) => &tx.inputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn conditional_inputs(&self) -> &[ConditionalInput] {
+        match self {
+            Transaction::V0(tx) => &tx.conditional_inputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn outputs(&self) -> &[Output] {
+        match self {
+            Transaction::V0(tx) => &tx.outputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn withdrawals(&self) -> &[MinimalWithdrawal] {
+        match self {
+            Transaction::V0(tx) => &tx.withdrawals,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    fn conditional_outputs(&self) -> &[ConditionalOutput] {
+        match self {
+            Transaction::V0(tx) => &tx.conditional_outputs,
+            Transaction::Reserved(_) => &[],
+        }
+    }
+
+    /// See `TransactionV0::sighash`. A reserved-version transaction has no
+    /// fields this node understands, so it hashes its opaque payload
+    /// instead.
+    fn sighash(&self, input_index: usize) -> Uint256 {
+        match self {
+            Transaction::V0(tx) => tx.sighash(input_index),
+            Transaction::Reserved(reserved) => (reserved.clone(), input_index).hash(),
+        }
+    }
+}
+
+/// The set of `Transaction`/`MinimalBody` versions this node has
+/// activated, beyond the always-on v0. A block whose `MinimalBody` or any
+/// of its transactions carries a version outside this set is rejected.
+#[derive(Debug, Default, Clone)]
+struct ActivatedVersions(HashSet<u32>);
+
+impl ActivatedVersions {
+    fn is_activated(&self, version: u32) -> bool {
+        version == 0 || self.0.contains(&version)
+    }
+
+    /// Accepts `version` in `validate_block` from now on.
+    fn activate(&mut self, version: u32) {
+        self.0.insert(version);
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct MinimalBody {
+    /// Lets consensus rules for the body's own shape evolve the same way
+    /// `Transaction`'s version does; `validate_block` rejects any value
+    /// other than `0` unless that version has been activated.
+    version: u32,
     coinbase: Vec<Output>,
     transactions: Vec<Transaction>,
 }
@@ -74,7 +536,7 @@ impl MinimalBody {
             .map(|(n, output)| (Outpoint::Coinbase { block_hash, n }, output.clone()));
         outputs.extend(coinbase_outputs);
         let regular_outputs = self.transactions.iter().flat_map(|tx| {
-            tx.outputs
+            tx.outputs()
                 .iter()
                 .enumerate()
                 .map(|(n, output)| (Outpoint::Regular { txid: tx.hash(), n }, output.clone()))
@@ -83,12 +545,179 @@ impl MinimalBody {
         outputs
     }
 
+    fn conditional_outputs(&self) -> HashMap<Outpoint, ConditionalOutput> {
+        self.transactions
+            .iter()
+            .flat_map(|tx| {
+                tx.conditional_outputs()
+                    .iter()
+                    .enumerate()
+                    .map(|(n, output)| {
+                        (Outpoint::Conditional { txid: tx.hash(), n }, output.clone())
+                    })
+            })
+            .collect()
+    }
+
     fn inputs(&self) -> Vec<MinimalInput> {
         self.transactions
             .iter()
-            .flat_map(|tx| tx.inputs.clone())
+            .flat_map(|tx| tx.inputs().to_vec())
             .collect()
     }
+
+    fn conditional_inputs(&self) -> Vec<ConditionalInput> {
+        self.transactions
+            .iter()
+            .flat_map(|tx| tx.conditional_inputs().to_vec())
+            .collect()
+    }
+
+    /// Per-input sighashes in the same flattened order as
+    /// `deposit_inputs()`, `refund_inputs()`, `inputs()`, and
+    /// `conditional_inputs()`, so each can be zipped directly against its
+    /// matching input.
+    fn sighashes(&self) -> (Vec<Uint256>, Vec<Uint256>, Vec<Uint256>, Vec<Uint256>) {
+        let mut deposit_sighashes = Vec::new();
+        let mut refund_sighashes = Vec::new();
+        let mut input_sighashes = Vec::new();
+        let mut conditional_sighashes = Vec::new();
+        for tx in &self.transactions {
+            let mut input_index = 0;
+            for _ in tx.deposit_inputs() {
+                deposit_sighashes.push(tx.sighash(input_index));
+                input_index += 1;
+            }
+            for _ in tx.refund_inputs() {
+                refund_sighashes.push(tx.sighash(input_index));
+                input_index += 1;
+            }
+            for _ in tx.inputs() {
+                input_sighashes.push(tx.sighash(input_index));
+                input_index += 1;
+            }
+            for _ in tx.conditional_inputs() {
+                conditional_sighashes.push(tx.sighash(input_index));
+                input_index += 1;
+            }
+        }
+        (
+            deposit_sighashes,
+            refund_sighashes,
+            input_sighashes,
+            conditional_sighashes,
+        )
+    }
+}
+
+/// A Merkle inclusion proof: the sibling hash at each level from leaf to
+/// root, paired with whether that sibling sits to the right of the node
+/// being folded up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MerkleProof {
+    siblings: Vec<(Uint256, bool)>,
+}
+
+fn merkle_parent(left: &Uint256, right: &Uint256) -> Uint256 {
+    (left.clone(), right.clone()).hash()
+}
+
+/// Computes a Bitcoin-style Merkle root: leaves are folded in pairs,
+/// duplicating the last node of a level when its length is odd, until a
+/// single root remains. A single leaf is its own root. An empty leaf set
+/// has no pairs to fold, so it commits to a fixed sentinel (the hash of an
+/// empty slice) rather than panicking. `MinimalBody::leaves()` always
+/// includes the body's version leaf, so `digest()` never passes an empty
+/// set here.
+fn merkle_root(leaves: &[Uint256]) -> Uint256 {
+    if leaves.is_empty() {
+        return Vec::<Uint256>::new().hash();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level.last().cloned().unwrap());
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| merkle_parent(&pair[0], &pair[1]))
+            .collect();
+    }
+    level.remove(0)
+}
+
+fn merkle_proof(leaves: &[Uint256], mut index: usize) -> MerkleProof {
+    let mut level = leaves.to_vec();
+    let mut siblings = Vec::new();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level.last().cloned().unwrap());
+        }
+        let sibling_is_right = index % 2 == 0;
+        let sibling_index = if sibling_is_right {
+            index + 1
+        } else {
+            index - 1
+        };
+        siblings.push((level[sibling_index].clone(), sibling_is_right));
+        level = level
+            .chunks(2)
+            .map(|pair| merkle_parent(&pair[0], &pair[1]))
+            .collect();
+        index /= 2;
+    }
+    MerkleProof { siblings }
+}
+
+fn verify_merkle_proof(root: &Uint256, leaf: &Uint256, proof: &MerkleProof) -> bool {
+    let node = proof
+        .siblings
+        .iter()
+        .fold(leaf.clone(), |node, (sibling, sibling_is_right)| {
+            if *sibling_is_right {
+                merkle_parent(&node, sibling)
+            } else {
+                merkle_parent(sibling, &node)
+            }
+        });
+    node == *root
+}
+
+impl MinimalBody {
+    /// The ordered leaves of the body's Merkle tree: `version` (so that a
+    /// body reinterpreted under a different version after the fact changes
+    /// `digest()`), followed by coinbase outputs, followed by each
+    /// transaction, matching the order `digest()` commits to.
+    fn leaves(&self) -> Vec<Uint256> {
+        let mut leaves: Vec<Uint256> = vec![self.version.hash()];
+        leaves.extend(self.coinbase.iter().map(|output| output.hash()));
+        leaves.extend(self.transactions.iter().map(|tx| tx.hash()));
+        leaves
+    }
+
+    /// A Merkle proof that the transaction carrying the withdrawal at
+    /// `index` (in the flattened order returned by `withdrawals()`) is
+    /// committed to by `digest()`, together with that transaction's hash
+    /// (the leaf the proof is over). A caller holding only the
+    /// `Withdrawal` can't recompute this leaf itself, so it's handed back
+    /// alongside the proof rather than left for the caller to derive.
+    /// Returns `None` if `index` is out of range.
+    fn withdrawal_proof(&self, index: usize) -> Option<(Uint256, MerkleProof)> {
+        let mut seen = 0;
+        for (tx_index, tx) in self.transactions.iter().enumerate() {
+            if index < seen + tx.withdrawals().len() {
+                let leaf_index = 1 + self.coinbase.len() + tx_index;
+                return Some((tx.hash(), merkle_proof(&self.leaves(), leaf_index)));
+            }
+            seen += tx.withdrawals().len();
+        }
+        None
+    }
+
+    /// Verifies that `leaf` is committed to by `root` via `proof`.
+    fn verify_proof(root: &Uint256, leaf: &Uint256, proof: &MerkleProof) -> bool {
+        verify_merkle_proof(root, leaf, proof)
+    }
 }
 
 type MinimalHeader = Header<<MinimalBody as Body<MinimalAddress>>::Digest>;
@@ -97,35 +726,129 @@ impl Body<MinimalAddress> for MinimalBody {
     type Digest = Uint256;
 
     fn digest(&self) -> Self::Digest {
-        self.hash()
+        merkle_root(&self.leaves())
     }
 
     fn withdrawals(&self) -> Vec<Withdrawal<MinimalAddress>> {
         self.transactions
             .iter()
-            .flat_map(|tx| tx.withdrawals.clone())
+            .flat_map(|tx| tx.withdrawals().to_vec())
             .collect()
     }
 
     fn deposit_inputs(&self) -> Vec<DepositInput<MinimalSignature>> {
         self.transactions
             .iter()
-            .flat_map(|tx| tx.deposit_inputs.clone())
+            .flat_map(|tx| tx.deposit_inputs().to_vec())
             .collect()
     }
 
     fn refund_inputs(&self) -> Vec<RefundInput<MinimalSignature>> {
         self.transactions
             .iter()
-            .flat_map(|tx| tx.refund_inputs.clone())
+            .flat_map(|tx| tx.refund_inputs().to_vec())
             .collect()
     }
 }
 
+/// Maturity window, in blocks, a withdrawal must sit for before a refund
+/// claiming it back can be connected.
+///
+/// Deviation from the request this implements: it asked for an *optional,
+/// per-withdrawal* relative timelock, matching `Output::timelock`.
+/// `Withdrawal` is an opaque sdk type with no spare field to carry one, so
+/// this ships as a single protocol-wide constant applied unconditionally
+/// to every withdrawal instead. Flagging this rather than treating it as
+/// equivalent: revisit if `sdk::Withdrawal` ever grows a field for it.
+const WITHDRAWAL_TIMELOCK: u64 = 144;
+
 #[derive(Debug, Default)]
 struct MinimalState {
     utxos: HashSet<Outpoint>,
     outputs: HashMap<Outpoint, Output>,
+    /// Kept around even after the output is spent, like `outputs`: only
+    /// `utxos` membership tracks whether it is currently spendable, so a
+    /// reorg's `disconnect` can un-spend it without having to reconstruct
+    /// the contract data from scratch.
+    conditional_outputs: HashMap<Outpoint, ConditionalOutput>,
+    /// The height each UTXO was created at, so `validate_block` can enforce
+    /// `Output::timelock` relative to the current header's height.
+    creation_heights: HashMap<Outpoint, u64>,
+    /// The height each withdrawal was first seen in a connected block's
+    /// transactions, so a refund claiming it back can have
+    /// `WITHDRAWAL_TIMELOCK` enforced against it. Keyed by the
+    /// withdrawal's own hash, since `Withdrawal` carries no outpoint of
+    /// its own in this crate; the value is every creation height recorded
+    /// under that hash rather than just one, since two distinct
+    /// withdrawals (e.g. the same amount and address) collide on the same
+    /// key and each still needs its own entry.
+    withdrawal_creation_heights: HashMap<Uint256, Vec<u64>>,
+    /// Transaction/body versions this node accepts beyond the always-on
+    /// v0. This would naturally live alongside other activation flags on
+    /// `MainState`, but that type is shared main-chain state outside this
+    /// crate, so it is tracked here instead.
+    activated_versions: ActivatedVersions,
+}
+
+impl MinimalState {
+    /// Whether `outpoint`, created at `creation_heights[outpoint]` and
+    /// carrying `timelock`, is spendable at `current_height`.
+    fn timelock_satisfied(
+        &self,
+        outpoint: &Outpoint,
+        timelock: Option<u64>,
+        current_height: u64,
+    ) -> bool {
+        let Some(timelock) = timelock else {
+            return true;
+        };
+        let Some(&creation_height) = self.creation_heights.get(outpoint) else {
+            return false;
+        };
+        current_height.saturating_sub(creation_height) >= timelock
+    }
+
+    /// Whether `withdrawal` has matured past `WITHDRAWAL_TIMELOCK` as of
+    /// `current_height`.
+    fn withdrawal_timelock_satisfied(
+        &self,
+        withdrawal: &MinimalWithdrawal,
+        current_height: u64,
+    ) -> bool {
+        self.withdrawal_hash_timelock_satisfied(&withdrawal.hash(), current_height)
+    }
+
+    /// The actual maturity check behind `withdrawal_timelock_satisfied`,
+    /// taking the withdrawal's hash directly rather than a `Withdrawal`
+    /// (an opaque sdk type this crate never constructs itself, only ever
+    /// receiving one by reference), so the logic can be exercised in tests
+    /// without it.
+    ///
+    /// Since the hash doesn't disambiguate distinct withdrawals with
+    /// identical content, this checks against the *most recent* creation
+    /// height recorded under that hash rather than an arbitrary one:
+    /// that's the only choice that can't let a still-immature withdrawal
+    /// ride on an older, already-matured one's height.
+    fn withdrawal_hash_timelock_satisfied(
+        &self,
+        withdrawal_hash: &Uint256,
+        current_height: u64,
+    ) -> bool {
+        let Some(creation_heights) = self.withdrawal_creation_heights.get(withdrawal_hash) else {
+            return false;
+        };
+        let Some(&most_recent_creation_height) = creation_heights.iter().max() else {
+            return false;
+        };
+        current_height.saturating_sub(most_recent_creation_height) >= WITHDRAWAL_TIMELOCK
+    }
+
+    /// Accepts blocks carrying `version` from now on. Versions activate
+    /// one at a time rather than through a bit-flag vector, matching how
+    /// `ActivatedVersions` stores them.
+    fn activate_version(&mut self, version: u32) {
+        self.activated_versions.activate(version);
+    }
 }
 
 impl SideState<MinimalAddress, MinimalBody> for MinimalState {
@@ -137,13 +860,28 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
         header: &MinimalHeader,
         body: &MinimalBody,
     ) -> bool {
+        let versions_activated = self.activated_versions.is_activated(body.version)
+            && body
+                .transactions
+                .iter()
+                .all(|tx| self.activated_versions.is_activated(tx.version()));
+        if !versions_activated {
+            return false;
+        }
+
         let inputs = body.inputs();
         let deposit_inputs = body.deposit_inputs();
         let refund_inputs = body.refund_inputs();
+        let conditional_inputs = body.conditional_inputs();
 
         let spent_outputs: Option<Vec<Output>> = inputs
             .iter()
-            .map(|input| self.outputs.get(&input.outpoint).cloned())
+            .map(|input| {
+                if !self.utxos.contains(&input.outpoint) {
+                    return None;
+                }
+                self.outputs.get(&input.outpoint).cloned()
+            })
             .collect();
         let claimed_deposits: Option<Vec<MinimalDeposit>> = deposit_inputs
             .iter()
@@ -153,28 +891,78 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
             .iter()
             .map(|input| main_state.get_withdrawal(&input.outpoint))
             .collect();
+        let claimed_conditional_outputs: Option<Vec<ConditionalOutput>> = conditional_inputs
+            .iter()
+            .map(|input| {
+                if !self.utxos.contains(&input.outpoint) {
+                    return None;
+                }
+                self.conditional_outputs.get(&input.outpoint).cloned()
+            })
+            .collect();
 
-        let (spent_outputs, claimed_deposits, refunded_withdrawals) =
-            match (spent_outputs, claimed_deposits, refunded_withdrawals) {
-                (Some(so), Some(cd), Some(rw)) => (so, cd, rw),
+        let (spent_outputs, claimed_deposits, refunded_withdrawals, claimed_conditional_outputs) =
+            match (
+                spent_outputs,
+                claimed_deposits,
+                refunded_withdrawals,
+                claimed_conditional_outputs,
+            ) {
+                (Some(so), Some(cd), Some(rw), Some(cco)) => (so, cd, rw, cco),
                 _ => return false,
             };
 
+        let timelocks_satisfied = inputs.iter().zip(&spent_outputs).all(|(input, output)| {
+            self.timelock_satisfied(&input.outpoint, output.timelock, header.height())
+        });
+        if !timelocks_satisfied {
+            return false;
+        }
+
+        let withdrawal_timelocks_satisfied = refunded_withdrawals
+            .iter()
+            .all(|withdrawal| self.withdrawal_timelock_satisfied(withdrawal, header.height()));
+        if !withdrawal_timelocks_satisfied {
+            return false;
+        }
+
+        let (deposit_sighashes, refund_sighashes, input_sighashes, conditional_sighashes) =
+            body.sighashes();
+
         let all_signatures_valid = {
-            let input_signatures_valid = inputs
-                .iter()
-                .zip(&spent_outputs)
-                .all(|(input, output)| output.address.check_signature(&input.signature));
+            let input_signatures_valid =
+                inputs.iter().zip(&spent_outputs).zip(&input_sighashes).all(
+                    |((input, output), message)| {
+                        output.address.check_signature(message, &input.signature)
+                    },
+                );
             let deposit_signatures_valid = deposit_inputs
                 .iter()
                 .zip(&claimed_deposits)
-                .all(|(input, output)| output.address().check_signature(&input.signature));
+                .zip(&deposit_sighashes)
+                .all(|((input, output), message)| {
+                    output.address().check_signature(message, &input.signature)
+                });
             let refund_signatures_valid = refund_inputs
                 .iter()
                 .zip(&refunded_withdrawals)
-                .all(|(input, output)| output.address().check_signature(&input.signature));
+                .zip(&refund_sighashes)
+                .all(|((input, output), message)| {
+                    output.address().check_signature(message, &input.signature)
+                });
+            let conditional_signatures_valid = conditional_inputs
+                .iter()
+                .zip(&claimed_conditional_outputs)
+                .zip(&conditional_sighashes)
+                .all(|((input, output), message)| {
+                    output.attestations_valid(&input.attestations)
+                        && output.address.check_signature(message, &input.signature)
+                });
 
-            input_signatures_valid && deposit_signatures_valid && refund_signatures_valid
+            input_signatures_valid
+                && deposit_signatures_valid
+                && refund_signatures_valid
+                && conditional_signatures_valid
         };
         if !all_signatures_valid {
             return false;
@@ -187,17 +975,26 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
                 .iter()
                 .map(|output| output.amount())
                 .sum();
+            let conditional_amount: u64 = claimed_conditional_outputs
+                .iter()
+                .map(|output| output.amount)
+                .sum();
 
-            spent_outputs_amount + deposits_amount + refunds_amount
+            spent_outputs_amount + deposits_amount + refunds_amount + conditional_amount
         };
         let total_output_amount = {
             let outputs = body.outputs(header);
             let withdrawals = body.withdrawals();
+            let conditional_outputs = body.conditional_outputs();
 
             let outputs_amount: u64 = outputs.values().map(|output| output.amount).sum();
             let withdrawals_amount: u64 = withdrawals.iter().map(|output| output.amount()).sum();
+            let conditional_outputs_amount: u64 = conditional_outputs
+                .values()
+                .map(|output| output.amount)
+                .sum();
 
-            outputs_amount + withdrawals_amount
+            outputs_amount + withdrawals_amount + conditional_outputs_amount
         };
         let total_coinbase_amount: u64 = body.coinbase.iter().map(|output| output.amount).sum();
 
@@ -211,9 +1008,26 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
         for input in body.inputs() {
             self.utxos.remove(&input.outpoint);
         }
+        for input in body.conditional_inputs() {
+            self.utxos.remove(&input.outpoint);
+        }
         let outputs = body.outputs(header);
         self.utxos.extend(outputs.keys().cloned());
+        self.creation_heights.extend(
+            outputs
+                .keys()
+                .map(|outpoint| (outpoint.clone(), header.height())),
+        );
         self.outputs.extend(outputs);
+        let conditional_outputs = body.conditional_outputs();
+        self.utxos.extend(conditional_outputs.keys().cloned());
+        self.conditional_outputs.extend(conditional_outputs);
+        for withdrawal in body.withdrawals() {
+            self.withdrawal_creation_heights
+                .entry(withdrawal.hash())
+                .or_default()
+                .push(header.height());
+        }
         Ok(())
     }
 
@@ -225,9 +1039,34 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
         let inputs = body.inputs();
         let spent_outpoints = inputs.iter().map(|input| input.outpoint.clone());
         self.utxos.extend(spent_outpoints);
+        let spent_conditional_outpoints = body
+            .conditional_inputs()
+            .into_iter()
+            .map(|input| input.outpoint);
+        self.utxos.extend(spent_conditional_outpoints);
         let outputs = body.outputs(header);
         for utxo in outputs.keys() {
             self.utxos.remove(utxo);
+            self.creation_heights.remove(utxo);
+        }
+        let conditional_outputs = body.conditional_outputs();
+        for utxo in conditional_outputs.keys() {
+            self.utxos.remove(utxo);
+        }
+        for withdrawal in body.withdrawals() {
+            if let Some(creation_heights) =
+                self.withdrawal_creation_heights.get_mut(&withdrawal.hash())
+            {
+                if let Some(position) = creation_heights
+                    .iter()
+                    .position(|&height| height == header.height())
+                {
+                    creation_heights.remove(position);
+                }
+                if creation_heights.is_empty() {
+                    self.withdrawal_creation_heights.remove(&withdrawal.hash());
+                }
+            }
         }
         Ok(())
     }
@@ -248,3 +1087,324 @@ impl SideState<MinimalAddress, MinimalBody> for MinimalState {
 //     coinbase: Vec<Output>,
 //     transactions: Vec<Transaction>,
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merkle_root_and_proof_round_trip() {
+        let leaves: Vec<Uint256> = (0..5u64).map(|i| i.to_be_bytes().hash()).collect();
+        let root = merkle_root(&leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = merkle_proof(&leaves, index);
+            assert!(verify_merkle_proof(&root, leaf, &proof));
+        }
+    }
+
+    #[test]
+    fn merkle_proof_rejects_wrong_leaf() {
+        let leaves: Vec<Uint256> = (0..5u64).map(|i| i.to_be_bytes().hash()).collect();
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 0);
+        assert!(!verify_merkle_proof(&root, &leaves[1], &proof));
+    }
+
+    #[test]
+    fn merkle_root_of_empty_leaves_does_not_panic() {
+        // An empty leaf set still produces a root (a fixed sentinel) instead
+        // of panicking.
+        let empty_sentinel = Vec::<Uint256>::new().hash();
+        assert_eq!(merkle_root(&[]), empty_sentinel);
+
+        // The sentinel must not collide with a real, non-empty root.
+        let leaves: Vec<Uint256> = (0..3u64).map(|i| i.to_be_bytes().hash()).collect();
+        assert_ne!(merkle_root(&leaves), empty_sentinel);
+    }
+
+    #[test]
+    fn cover_range_is_exact_and_minimal() {
+        let base = 10;
+        let num_digits = 4;
+        let prefixes = cover_range(37, 1042, base, num_digits);
+
+        let mut covered = HashSet::new();
+        for prefix in &prefixes {
+            let subtree_size = base.pow(num_digits - prefix.digits.len() as u32);
+            let subtree_start = digits_to_value(&prefix.digits, base) * subtree_size;
+            for value in subtree_start..subtree_start + subtree_size {
+                assert!(
+                    covered.insert(value),
+                    "{value} covered by more than one prefix"
+                );
+            }
+        }
+        assert_eq!(covered, (37..=1042).collect());
+
+        // 0037-0039 (3 leaves), 0040-0099 (6), 0100-0999 (9),
+        // 1000-1039 (4), 1040-1042 (3 leaves).
+        assert_eq!(prefixes.len(), 25);
+    }
+
+    #[test]
+    fn contract_conditional_outputs_round_trip_through_attestations_valid() {
+        let secp = secp256k1::Secp256k1::new();
+        let attestation_key = secp256k1::SecretKey::from_slice(&[3; 32]).unwrap();
+        let contract = Contract {
+            oracle: Oracle {
+                announcement_public_key: secp256k1::PublicKey::from_secret_key(
+                    &secp,
+                    &secp256k1::SecretKey::from_slice(&[4; 32]).unwrap(),
+                ),
+                attestation_public_key: secp256k1::PublicKey::from_secret_key(
+                    &secp,
+                    &attestation_key,
+                ),
+            },
+            base: 10,
+            num_digits: 2,
+        };
+        let outputs = contract.conditional_outputs(37, 52, 1, MinimalAddress::default());
+        assert!(!outputs.is_empty());
+
+        // The oracle attests to the true outcome, 42, one digit at a time.
+        let sign = |position: usize, digit: u64| {
+            let message = (contract.clone(), position, digit).hash();
+            let message = secp256k1::Message::from_digest(*message.as_ref());
+            secp.sign_ecdsa(&message, &attestation_key)
+        };
+        let true_digits = [4u64, 2];
+        let attestations: Vec<_> = true_digits
+            .iter()
+            .enumerate()
+            .map(|(position, &digit)| sign(position, digit))
+            .collect();
+
+        // Exactly one of the generated outputs' prefixes matches the true
+        // outcome, however `cover_range` split up the interval.
+        let matching = outputs
+            .iter()
+            .filter(|output| output.attestations_valid(&attestations[..output.prefix.digits.len()]))
+            .count();
+        assert_eq!(matching, 1);
+    }
+
+    #[test]
+    fn attestations_valid_binds_to_contract_and_position() {
+        let secp = secp256k1::Secp256k1::new();
+        let announcement_key = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let attestation_key = secp256k1::SecretKey::from_slice(&[2; 32]).unwrap();
+        let contract = Contract {
+            oracle: Oracle {
+                announcement_public_key: secp256k1::PublicKey::from_secret_key(
+                    &secp,
+                    &announcement_key,
+                ),
+                attestation_public_key: secp256k1::PublicKey::from_secret_key(
+                    &secp,
+                    &attestation_key,
+                ),
+            },
+            base: 10,
+            num_digits: 2,
+        };
+        let output = ConditionalOutput {
+            contract: contract.clone(),
+            prefix: DigitPrefix { digits: vec![5, 7] },
+            amount: 1,
+            address: MinimalAddress::default(),
+        };
+        let sign = |position: usize, digit: u64| {
+            let message = (contract.clone(), position, digit).hash();
+            let message = secp256k1::Message::from_digest(*message.as_ref());
+            secp.sign_ecdsa(&message, &attestation_key)
+        };
+
+        let real_outcome = vec![sign(0, 5), sign(1, 7)];
+        assert!(output.attestations_valid(&real_outcome));
+
+        // Each signature is individually valid for its own (position, digit),
+        // but reordering them must not satisfy a prefix whose positions were
+        // never actually attested to this way.
+        let reordered = vec![sign(1, 7), sign(0, 5)];
+        assert!(!output.attestations_valid(&reordered));
+
+        // The same oracle and prefix under a different contract must not
+        // accept signatures made for this one.
+        let other_output = ConditionalOutput {
+            contract: Contract {
+                base: 16,
+                ..contract.clone()
+            },
+            ..output.clone()
+        };
+        assert!(!other_output.attestations_valid(&real_outcome));
+    }
+
+    // `validate_block`'s version gate (src/main.rs) is exactly
+    // `self.activated_versions.is_activated(...)` for the body and every
+    // transaction's version, so exercising that gate directly here covers
+    // the reject-then-activate-then-accept invariant without needing to
+    // construct a `sdk::Header`/`MainState`, which this crate never builds
+    // itself (it only ever receives them by reference from the sdk).
+    #[test]
+    fn version_is_rejected_until_activated_then_accepted() {
+        let mut activated = ActivatedVersions::default();
+        assert!(activated.is_activated(0));
+        assert!(!activated.is_activated(1));
+
+        activated.activate(1);
+        assert!(activated.is_activated(1));
+    }
+
+    #[test]
+    fn minimal_state_activate_version_unlocks_the_version_gate() {
+        let mut state = MinimalState::default();
+        assert!(!state.activated_versions.is_activated(1));
+
+        state.activate_version(1);
+        assert!(state.activated_versions.is_activated(1));
+    }
+
+    #[test]
+    fn timelock_satisfied_enforces_the_maturity_window() {
+        let mut state = MinimalState::default();
+        let outpoint = Outpoint::Regular {
+            txid: "tx".hash(),
+            n: 0,
+        };
+        state.creation_heights.insert(outpoint.clone(), 10);
+
+        // No timelock at all is always spendable.
+        assert!(state.timelock_satisfied(&outpoint, None, 10));
+
+        // A timelocked outpoint is not spendable before it matures...
+        assert!(!state.timelock_satisfied(&outpoint, Some(5), 14));
+        // ...and is spendable from the block it matures on, onward.
+        assert!(state.timelock_satisfied(&outpoint, Some(5), 15));
+        assert!(state.timelock_satisfied(&outpoint, Some(5), 20));
+
+        // An outpoint this state never saw created can't be spent under a
+        // timelock, even if the height would otherwise satisfy it.
+        let unknown = Outpoint::Regular {
+            txid: "other tx".hash(),
+            n: 0,
+        };
+        assert!(!state.timelock_satisfied(&unknown, Some(5), 1000));
+    }
+
+    #[test]
+    fn withdrawal_hash_timelock_satisfied_enforces_the_maturity_window() {
+        let mut state = MinimalState::default();
+        let withdrawal_hash: Uint256 = "withdrawal".hash();
+        state
+            .withdrawal_creation_heights
+            .insert(withdrawal_hash.clone(), vec![10]);
+
+        assert!(!state
+            .withdrawal_hash_timelock_satisfied(&withdrawal_hash, 10 + WITHDRAWAL_TIMELOCK - 1));
+        assert!(
+            state.withdrawal_hash_timelock_satisfied(&withdrawal_hash, 10 + WITHDRAWAL_TIMELOCK)
+        );
+
+        // A hash this state never recorded a creation height for can't
+        // mature under any height.
+        let unknown: Uint256 = "other withdrawal".hash();
+        assert!(!state.withdrawal_hash_timelock_satisfied(&unknown, 1_000_000));
+    }
+
+    #[test]
+    fn withdrawal_hash_timelock_satisfied_uses_the_most_recent_collision() {
+        // Two distinct withdrawals with identical content (e.g. the same
+        // amount and address) hash the same, so they share one entry in
+        // `withdrawal_creation_heights` carrying both creation heights.
+        // The earlier one having matured must not let the later,
+        // still-immature one be refunded early.
+        let mut state = MinimalState::default();
+        let withdrawal_hash: Uint256 = "repeated withdrawal".hash();
+        state
+            .withdrawal_creation_heights
+            .insert(withdrawal_hash.clone(), vec![0, 10]);
+
+        let current_height = WITHDRAWAL_TIMELOCK;
+        assert!(current_height.saturating_sub(0) >= WITHDRAWAL_TIMELOCK);
+        assert!(current_height.saturating_sub(10) < WITHDRAWAL_TIMELOCK);
+        assert!(!state.withdrawal_hash_timelock_satisfied(&withdrawal_hash, current_height));
+
+        assert!(
+            state.withdrawal_hash_timelock_satisfied(&withdrawal_hash, 10 + WITHDRAWAL_TIMELOCK)
+        );
+    }
+
+    #[test]
+    fn multisig_check_signature_enforces_the_threshold() {
+        let secp = secp256k1::Secp256k1::new();
+        let secret_keys: Vec<_> = (1u8..=3)
+            .map(|b| secp256k1::SecretKey::from_slice(&[b; 32]).unwrap())
+            .collect();
+        let public_keys: Vec<_> = secret_keys
+            .iter()
+            .map(|sk| secp256k1::PublicKey::from_secret_key(&secp, sk))
+            .collect();
+        let threshold = 2;
+        let address = MultisigAddress::new(threshold, public_keys.clone());
+
+        let message: Uint256 = "multisig sighash".hash();
+        let digest = secp256k1::Message::from_digest(*message.as_ref());
+        let sign = |secret_key: &secp256k1::SecretKey| secp.sign_ecdsa(&digest, secret_key);
+
+        let signature = |sigs: Vec<secp256k1::ecdsa::Signature>| MultisigSignature {
+            threshold,
+            member_public_keys: public_keys.clone(),
+            signatures: sigs,
+        };
+
+        // Exactly `threshold` distinct valid signatures passes.
+        let exactly_threshold = signature(vec![sign(&secret_keys[0]), sign(&secret_keys[1])]);
+        assert!(address.check_signature(&message, &exactly_threshold));
+
+        // One short of `threshold` fails.
+        let one_short = signature(vec![sign(&secret_keys[0])]);
+        assert!(!address.check_signature(&message, &one_short));
+
+        // Repeating the same key's signature doesn't count twice toward
+        // the threshold.
+        let duplicated = signature(vec![sign(&secret_keys[0]), sign(&secret_keys[0])]);
+        assert!(!address.check_signature(&message, &duplicated));
+
+        // All members signing, exceeding `threshold`, still passes.
+        let all_members = signature(secret_keys.iter().map(sign).collect());
+        assert!(address.check_signature(&message, &all_members));
+    }
+
+    #[test]
+    fn multisig_address_dedupes_repeated_member_keys() {
+        let secp = secp256k1::Secp256k1::new();
+        let secret_keys: Vec<_> = (1u8..=2)
+            .map(|b| secp256k1::SecretKey::from_slice(&[b; 32]).unwrap())
+            .collect();
+        let public_keys: Vec<_> = secret_keys
+            .iter()
+            .map(|sk| secp256k1::PublicKey::from_secret_key(&secp, sk))
+            .collect();
+
+        // Listing a key twice must not create extra signature slots: the
+        // address is the same as if it had been listed once.
+        let with_duplicate =
+            MultisigAddress::new(2, vec![public_keys[0], public_keys[0], public_keys[1]]);
+        let distinct = MultisigAddress::new(2, public_keys.clone());
+        assert_eq!(with_duplicate, distinct);
+
+        // The lone holder of `public_keys[0]` can't reach a threshold of 2
+        // by resubmitting the duplicated key slot with the same signature.
+        let message: Uint256 = "multisig sighash".hash();
+        let digest = secp256k1::Message::from_digest(*message.as_ref());
+        let sig = secp.sign_ecdsa(&digest, &secret_keys[0]);
+        let signature = MultisigSignature {
+            threshold: 2,
+            member_public_keys: vec![public_keys[0], public_keys[0], public_keys[1]],
+            signatures: vec![sig, sig],
+        };
+        assert!(!with_duplicate.check_signature(&message, &signature));
+    }
+}